@@ -0,0 +1,76 @@
+//metric tree over Hamming distance between 64-bit hashes, giving sublinear
+//neighbor lookups instead of comparing every pair with a naive O(n^2) scan
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct BkTree {
+    root: Option<Box<Node>>,
+}
+
+struct Node {
+    hash: u64,
+    //keyed by distance from this node, as the BK-tree invariant requires
+    children: HashMap<u32, Box<Node>>,
+}
+
+impl BkTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, hash: u64) {
+        match &mut self.root {
+            None => self.root = Some(Box::new(Node::leaf(hash))),
+            Some(root) => root.insert(hash),
+        }
+    }
+
+    //returns every inserted hash within `max_distance` Hamming bits of `query`
+    pub fn find_within(&self, query: u64, max_distance: u32) -> Vec<u64> {
+        let mut results = Vec::new();
+        if let Some(root) = &self.root {
+            root.find_within(query, max_distance, &mut results);
+        }
+        results
+    }
+}
+
+impl Node {
+    fn leaf(hash: u64) -> Self {
+        Node {
+            hash,
+            children: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let distance = hamming_distance(self.hash, hash);
+        match self.children.get_mut(&distance) {
+            Some(child) => child.insert(hash),
+            None => {
+                self.children.insert(distance, Box::new(Node::leaf(hash)));
+            }
+        }
+    }
+
+    fn find_within(&self, query: u64, max_distance: u32, results: &mut Vec<u64>) {
+        let distance = hamming_distance(self.hash, query);
+        if distance <= max_distance {
+            results.push(self.hash);
+        }
+
+        //the triangle inequality bounds which child buckets can possibly contain
+        //a match, so we skip the rest entirely
+        let lower = distance.saturating_sub(max_distance);
+        let upper = distance + max_distance;
+        for bucket in lower..=upper {
+            if let Some(child) = self.children.get(&bucket) {
+                child.find_within(query, max_distance, results);
+            }
+        }
+    }
+}
+
+fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
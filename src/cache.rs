@@ -0,0 +1,75 @@
+//persistent store of previously-computed full file hashes
+use std::{collections::HashMap, fs, path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::Digest;
+
+//bump this (or key off the active hash algorithm) whenever the on-disk format
+//or the hashing scheme changes, so stale caches are invalidated rather than misread
+const CACHE_FILE_NAME: &str = "stupdedup_hash_cache.json";
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    mtime: SystemTime,
+    hash: Digest,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HashCache {
+    //identifies which hash algorithm produced `entries`; a cache from a different
+    //algorithm is discarded rather than trusted
+    algorithm: String,
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl HashCache {
+    pub fn load(algorithm: &str) -> Self {
+        let empty = || HashCache {
+            algorithm: algorithm.to_string(),
+            entries: HashMap::new(),
+        };
+
+        let Some(path) = Self::cache_path() else {
+            return empty();
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            return empty();
+        };
+        match serde_json::from_str::<HashCache>(&contents) {
+            Ok(cache) if cache.algorithm == algorithm => cache,
+            //either unparsable or produced by a different hash algorithm
+            _ => empty(),
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::cache_path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string(self) {
+            let _ = fs::write(path, json);
+        }
+    }
+
+    pub fn get(&self, path: &PathBuf, size: u64, mtime: SystemTime) -> Option<Digest> {
+        let entry = self.entries.get(path)?;
+        if entry.size == size && entry.mtime == mtime {
+            Some(entry.hash.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, path: PathBuf, size: u64, mtime: SystemTime, hash: Digest) {
+        self.entries.insert(path, CacheEntry { size, mtime, hash });
+    }
+
+    fn cache_path() -> Option<PathBuf> {
+        Some(dirs::cache_dir()?.join(CACHE_FILE_NAME))
+    }
+}
@@ -0,0 +1,63 @@
+//directory and extension filters applied during the WalkDir traversal
+use std::collections::HashSet;
+
+use glob::Pattern;
+use walkdir::DirEntry;
+
+pub struct TraversalFilters {
+    exclude_dirs: Vec<Pattern>,
+    exclude_exts: HashSet<String>,
+    //when set, only these extensions are kept; anything else is dropped
+    only_exts: Option<HashSet<String>>,
+}
+
+impl TraversalFilters {
+    pub fn new(exclude_dir: &[String], exclude_ext: &[String], only_ext: &[String]) -> Self {
+        TraversalFilters {
+            exclude_dirs: exclude_dir
+                .iter()
+                .filter_map(|glob| match Pattern::new(glob) {
+                    Ok(pattern) => Some(pattern),
+                    Err(e) => {
+                        println!("Ignoring invalid --exclude-dir glob \"{glob}\": {e}");
+                        None
+                    }
+                })
+                .collect(),
+            exclude_exts: exclude_ext.iter().map(|ext| normalize_ext(ext)).collect(),
+            only_exts: (!only_ext.is_empty())
+                .then(|| only_ext.iter().map(|ext| normalize_ext(ext)).collect()),
+        }
+    }
+
+    //used as a WalkDir `filter_entry` predicate: returning false here prunes the
+    //whole subtree without ever descending into it
+    pub fn allows_dir_entry(&self, entry: &DirEntry) -> bool {
+        if !entry.file_type().is_dir() {
+            return true;
+        }
+        let Some(name) = entry.file_name().to_str() else {
+            return true;
+        };
+        !self
+            .exclude_dirs
+            .iter()
+            .any(|pattern| pattern.matches(name))
+    }
+
+    pub fn allows_file(&self, path: &std::path::Path) -> bool {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if let Some(only_exts) = &self.only_exts {
+            return only_exts.contains(&ext);
+        }
+        !self.exclude_exts.contains(&ext)
+    }
+}
+
+fn normalize_ext(ext: &str) -> String {
+    ext.trim_start_matches('.').to_lowercase()
+}
@@ -0,0 +1,42 @@
+//replaces a duplicate with a hardlink to its original
+use std::{fs, io, path::Path};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+//links to a temp name, then renames over the duplicate, so an interrupted
+//run never leaves it half-written
+pub fn replace_with_hardlink(original: &Path, dup: &Path) -> io::Result<()> {
+    if !same_filesystem(original, dup)? {
+        return Err(io::Error::other(
+            "original and duplicate are on different filesystems",
+        ));
+    }
+
+    let dir = dup.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = dup
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "duplicate has no file name"))?;
+    let tmp_path = dir.join(format!(".{}.stupdedup-tmp", file_name.to_string_lossy()));
+
+    fs::hard_link(original, &tmp_path)?;
+    match fs::rename(&tmp_path, dup) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> io::Result<bool> {
+    Ok(fs::metadata(a)?.dev() == fs::metadata(b)?.dev())
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> io::Result<bool> {
+    //no portable st_dev equivalent here; fall through and let the hardlink
+    //syscall itself report a cross-device failure
+    Ok(true)
+}
@@ -0,0 +1,67 @@
+//pluggable hash algorithm used to confirm duplicates
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HashAlgorithm {
+    //fast, non-cryptographic, the long-standing default
+    Gxhash,
+    //fast, non-cryptographic, wider adoption than gxhash
+    Xxh3,
+    //cryptographic, 256-bit digest, for when collisions must not happen
+    Blake3,
+}
+
+pub type Digest = Vec<u8>;
+
+//streaming hasher fed in fixed-size blocks rather than a single slice
+pub enum IncrementalHasher {
+    Gxhash(gxhash::GxHasher),
+    //boxed: Xxh3 and Hasher are far larger than GxHasher, and an enum
+    //always pays for its largest member's stack space
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl IncrementalHasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Gxhash => {
+                IncrementalHasher::Gxhash(gxhash::GxHasher::with_seed(super::HASH_SEED))
+            }
+            HashAlgorithm::Xxh3 => {
+                IncrementalHasher::Xxh3(Box::new(xxhash_rust::xxh3::Xxh3::new()))
+            }
+            HashAlgorithm::Blake3 => IncrementalHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            IncrementalHasher::Gxhash(h) => std::hash::Hasher::write(h, data),
+            IncrementalHasher::Xxh3(h) => h.update(data),
+            IncrementalHasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> Digest {
+        match self {
+            IncrementalHasher::Gxhash(h) => std::hash::Hasher::finish(&h).to_le_bytes().to_vec(),
+            IncrementalHasher::Xxh3(h) => h.digest().to_le_bytes().to_vec(),
+            IncrementalHasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    //stable identifier stored alongside cached hashes, so a cache built with a
+    //different algorithm is never mistaken for a match
+    pub fn cache_id(self) -> &'static str {
+        match self {
+            HashAlgorithm::Gxhash => "gxhash64",
+            HashAlgorithm::Xxh3 => "xxh3",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//finds visually similar images via perceptual hash (dHash) and a BK-tree
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use image::imageops::FilterType;
+
+use crate::{bktree::BkTree, FileInfo};
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "webp", "tiff"];
+//dHash grid: one extra column so each row yields 8 adjacent-pixel comparisons
+const HASH_GRID_WIDTH: u32 = 9;
+const HASH_GRID_HEIGHT: u32 = 8;
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+//downscales to a small fixed grid, converts to grayscale, and sets each bit by
+//comparing adjacent pixel luminances along a row
+pub fn dhash(path: &Path) -> Option<u64> {
+    let image = image::open(path).ok()?;
+    let small = image
+        .resize_exact(HASH_GRID_WIDTH, HASH_GRID_HEIGHT, FilterType::Triangle)
+        .into_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..HASH_GRID_HEIGHT {
+        for x in 0..HASH_GRID_WIDTH - 1 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    Some(hash)
+}
+
+//groups each not-yet-claimed hash with every unclaimed hash within `max_distance`
+//Hamming bits of it (so two members of a cluster may be up to twice that far
+//apart), picking the highest-resolution image in each cluster as the original
+pub fn find_near_duplicates(
+    files: &HashSet<FileInfo>,
+    max_distance: u32,
+) -> HashMap<&FileInfo, Vec<&FileInfo>> {
+    let mut tree = BkTree::new();
+    let mut by_hash: HashMap<u64, Vec<&FileInfo>> = HashMap::new();
+
+    for file in files.iter().filter(|file| is_image(&file.path)) {
+        let Some(hash) = dhash(&file.path) else {
+            continue;
+        };
+        if !by_hash.contains_key(&hash) {
+            tree.insert(hash);
+        }
+        by_hash.entry(hash).or_default().push(file);
+    }
+
+    let mut claimed = HashSet::new();
+    let mut groups = HashMap::new();
+
+    for &hash in by_hash.keys() {
+        if claimed.contains(&hash) {
+            continue;
+        }
+
+        //a neighbor already absorbed into an earlier cluster must not be
+        //pulled into this one too, or its files end up duplicated across
+        //two different "original"s
+        let mut cluster: Vec<&FileInfo> = Vec::new();
+        for neighbor in tree.find_within(hash, max_distance) {
+            if claimed.insert(neighbor) {
+                if let Some(files) = by_hash.get(&neighbor) {
+                    cluster.extend(files.iter().copied());
+                }
+            }
+        }
+
+        if cluster.len() < 2 {
+            continue;
+        }
+
+        cluster.sort_by_key(|file| std::cmp::Reverse(pixel_count(&file.path)));
+        let original = cluster.remove(0);
+        groups.insert(original, cluster);
+    }
+
+    groups
+}
+
+fn pixel_count(path: &Path) -> u64 {
+    image::image_dimensions(path)
+        .map(|(width, height)| u64::from(width) * u64::from(height))
+        .unwrap_or(0)
+}
@@ -1,16 +1,34 @@
+mod bktree;
+mod cache;
+mod filters;
+mod hardlink;
+mod hashing;
+mod image_dedup;
+mod report;
+
 use std::{
     collections::{HashMap, HashSet},
-    fs::{self, File},
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
     sync::{mpsc::channel, Arc, Mutex},
     thread::{self},
+    time::SystemTime,
 };
 
+use cache::HashCache;
 use clap::{Parser, ValueEnum};
+use filters::TraversalFilters;
+use hashing::{Digest, HashAlgorithm, IncrementalHasher};
 use itertools::Itertools;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
 const HASH_SEED: i64 = 0xBA647A7A;
+//size of the prefix read in the cheap partial-hash stage
+const PARTIAL_HASH_BYTES: usize = 4096;
+//block size used when streaming a file through the full hasher
+const STREAM_BLOCK_BYTES: usize = 64 * 1024;
 
 #[derive(Parser)]
 struct Args {
@@ -36,9 +54,47 @@ struct Args {
     #[arg(long)]
     delete: bool,
 
+    //replace duplicates with hardlinks to the original instead of deleting them
+    #[arg(long)]
+    hardlink: bool,
+
     //logs reassignments of "original" files
     #[arg(long)]
     reassigns: bool,
+
+    //group by content (full hash) instead of by filename, catching byte-identical
+    //files that happen to have unrelated names
+    #[arg(long = "by-content")]
+    by_content: bool,
+
+    //hash algorithm used to confirm duplicates; defaults to the fast non-crypto gxhash
+    #[arg(long = "hash")]
+    hash: Option<HashAlgorithm>,
+
+    //opt-in perceptual-hash mode: find visually similar images instead of
+    //byte-identical files
+    #[arg(long)]
+    images: bool,
+
+    //max Hamming distance between dHashes for two images to count as near-duplicates
+    #[arg(long = "image-distance", default_value_t = 10)]
+    image_distance: u32,
+
+    //write a machine-readable JSON report of the duplicate groups to this path
+    #[arg(long = "json")]
+    json: Option<std::path::PathBuf>,
+
+    //skip directories matching this glob (e.g. ".git", "node_modules"); repeatable
+    #[arg(long = "exclude-dir")]
+    exclude_dir: Vec<String>,
+
+    //skip files with this extension; repeatable
+    #[arg(long = "exclude-ext")]
+    exclude_ext: Vec<String>,
+
+    //keep only files with this extension; repeatable. Takes precedence over --exclude-ext
+    #[arg(long = "only-ext")]
+    only_ext: Vec<String>,
 }
 
 #[derive(Clone, ValueEnum)]
@@ -51,40 +107,89 @@ enum Filter {
     Onlynum,
 }
 
+//which pass decides what counts as a "duplicate"
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CheckingMethod {
+    //match on name_undup + size, same as always
+    Name,
+    //ignore names entirely, match on size then full content hash
+    Content,
+    //perceptual-hash match on image content, not an exact one
+    Images,
+}
+
+impl CheckingMethod {
+    fn from_args(args: &Args) -> Self {
+        if args.images {
+            CheckingMethod::Images
+        } else if args.by_content {
+            CheckingMethod::Content
+        } else {
+            CheckingMethod::Name
+        }
+    }
+}
+
 #[derive(Hash, PartialEq, Eq, Clone, Debug)]
 struct FileInfo {
     name: String,
     name_undup: String,
     path: std::path::PathBuf,
     size: u64,
+    mtime: SystemTime,
 }
 
 fn main() {
     let args = Args::parse();
+    let method = CheckingMethod::from_args(&args);
+    let algorithm = args.hash.unwrap_or(HashAlgorithm::Gxhash);
+    let cache = Mutex::new(HashCache::load(algorithm.cache_id()));
+
+    let traversal_filters =
+        TraversalFilters::new(&args.exclude_dir, &args.exclude_ext, &args.only_ext);
+    let files = read_fileinfos(&args.path, &traversal_filters);
+
+    let filtered = match method {
+        CheckingMethod::Name => {
+            println!("File info collected, finding duplicates based on filename and size");
+            let (originals, duplicates) = dedup_name_size(&files, args.reassigns);
+
+            println!("Deduplication step 1 complete");
+            println!("Filtering the results...");
+            let filter = get_filter(&args.filter);
+
+            let mut filtered = duplicates
+                .into_iter()
+                .filter(filter)
+                //deref original to avoid double reference
+                .map(|dup| (*originals.get(&dup.name_undup).unwrap(), dup))
+                .into_group_map();
+            println!("Filtered and grouped duplicates");
+
+            if !args.nohash {
+                println!("Checking file hashes");
+                let discarded = check_hashes(&mut filtered, args.print_hash, &cache, algorithm);
+                //clear the map entries with empty duplicates
+                filtered.retain(|_, dups| !dups.is_empty());
+                println!("Discarded {discarded} false duplicates");
+            }
+            filtered
+        }
+        CheckingMethod::Content => {
+            println!("File info collected, finding duplicates based on content only");
+            let filtered = dedup_by_content(&files, &cache, algorithm);
+            println!("Grouped duplicates by content hash");
+            filtered
+        }
+        CheckingMethod::Images => {
+            println!("File info collected, finding visually similar images");
+            let filtered = image_dedup::find_near_duplicates(&files, args.image_distance);
+            println!("Grouped near-duplicate images");
+            filtered
+        }
+    };
 
-    let files = read_fileinfos(&args.path);
-    println!("File info collected, finding duplicates based on filename and size");
-    let (originals, duplicates) = dedup_name_size(&files, args.reassigns);
-
-    println!("Deduplication step 1 complete");
-    println!("Filtering the results...");
-    let filter = get_filter(&args.filter);
-
-    let mut filtered = duplicates
-        .into_iter()
-        .filter(filter)
-        //deref original to avoid double reference
-        .map(|dup| (*originals.get(&dup.name_undup).unwrap(), dup))
-        .into_group_map();
-    println!("Filtered and grouped duplicates");
-
-    if !args.nohash {
-        println!("Checking file hashes");
-        let discarded = check_hashes(&mut filtered, args.print_hash);
-        //clear the map entries with empty duplicates
-        filtered.retain(|_, dups| !dups.is_empty());
-        println!("Discarded {discarded} false duplicates");
-    }
+    cache.lock().unwrap().save();
 
     //Do things with the duplicates
     if args.print {
@@ -98,6 +203,17 @@ fn main() {
             });
     }
 
+    if let Some(json_path) = &args.json {
+        match report::write_json(json_path, &filtered) {
+            Ok(()) => println!("Wrote JSON report to {}", json_path.display()),
+            Err(e) => println!(
+                "Error writing JSON report to {}:\n{}",
+                json_path.display(),
+                e
+            ),
+        }
+    }
+
     let dup_count: usize = filtered
         .values() //duplicates vec
         .map(|dups| dups.len())
@@ -105,8 +221,18 @@ fn main() {
 
     println!("Found a total of {} duplicates.", dup_count);
 
-    //delete
-    if args.delete {
+    //hardlink takes priority over delete: both reclaim space, but hardlinking
+    //keeps every path alive
+    if args.hardlink {
+        filtered.iter().for_each(|(original, dups)| {
+            dups.iter().for_each(|dup| {
+                match hardlink::replace_with_hardlink(&original.path, &dup.path) {
+                    Ok(()) => (),
+                    Err(e) => println!("Error hardlinking {}:\n{}", dup.path.display(), e),
+                }
+            })
+        });
+    } else if args.delete {
         filtered.iter().for_each(|(_, dups)| {
             dups.iter()
                 .for_each(|dup| match std::fs::remove_file(&dup.path) {
@@ -172,10 +298,43 @@ fn dedup_name_size(
     (originals, duplicates)
 }
 
-fn read_fileinfos(path: &str) -> HashSet<FileInfo> {
+//groups files purely by size, then confirms each size-group by full content hash;
+//within a confirmed group the shortest path is kept as the "original"
+fn dedup_by_content<'a>(
+    files: &'a HashSet<FileInfo>,
+    cache: &Mutex<HashCache>,
+    algorithm: HashAlgorithm,
+) -> HashMap<&'a FileInfo, Vec<&'a FileInfo>> {
+    let by_size: HashMap<u64, Vec<&FileInfo>> = files.iter().into_group_map_by(|file| file.size);
+
+    by_size
+        .into_par_iter()
+        .filter(|(_size, group)| group.len() > 1)
+        .flat_map(|(_size, group)| {
+            let by_hash: HashMap<Digest, Vec<&FileInfo>> = group
+                .into_iter()
+                .filter_map(|file| Some((cached_full_hash(cache, file, algorithm)?, file)))
+                .into_group_map();
+
+            by_hash
+                .into_par_iter()
+                .filter(|(_hash, cluster)| cluster.len() > 1)
+                .map(|(_hash, mut cluster)| {
+                    cluster.sort_by_key(|file| file.path.as_os_str().len());
+                    let original = cluster.remove(0);
+                    (original, cluster)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn read_fileinfos(path: &str, filters: &TraversalFilters) -> HashSet<FileInfo> {
     let mut count = 0;
     let iter = WalkDir::new(path)
         .into_iter()
+        //prunes excluded directories before WalkDir ever descends into them
+        .filter_entry(|entry| filters.allows_dir_entry(entry))
         .filter_map(|e| match e {
             Ok(file) => Some(file),
             Err(e) => {
@@ -184,6 +343,7 @@ fn read_fileinfos(path: &str) -> HashSet<FileInfo> {
             }
         })
         .filter(|e| e.file_type().is_file())
+        .filter(|e| filters.allows_file(e.path()))
         .inspect(|_| {
             count += 1;
             print!("\rRead {} files", count);
@@ -195,12 +355,13 @@ fn read_fileinfos(path: &str) -> HashSet<FileInfo> {
 
             let name = pathname.file_name()?.to_string_lossy().into_owned();
             let undup_name = get_undestroyed_name(&name).to_string();
-            let size = File::open(entry.path()).ok()?.metadata().unwrap().len();
+            let metadata = File::open(entry.path()).ok()?.metadata().unwrap();
 
             Some(FileInfo {
                 name,
                 name_undup: undup_name,
-                size,
+                size: metadata.len(),
+                mtime: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
                 path: entry.path().to_path_buf(),
             })
         });
@@ -241,7 +402,12 @@ enum HashingUpdate {
 }
 
 //returns amount of false duplicates
-fn check_hashes(dupmap: &mut HashMap<&FileInfo, Vec<&FileInfo>>, print: bool) -> u32 {
+fn check_hashes(
+    dupmap: &mut HashMap<&FileInfo, Vec<&FileInfo>>,
+    print: bool,
+    cache: &Mutex<HashCache>,
+    algorithm: HashAlgorithm,
+) -> u32 {
     let count = Arc::new(Mutex::new(0));
     let origs = dupmap.len();
     let (send, receive) = channel();
@@ -268,15 +434,11 @@ fn check_hashes(dupmap: &mut HashMap<&FileInfo, Vec<&FileInfo>>, print: bool) ->
             }
         });
         dupmap.par_iter_mut().for_each(|(original, duplicates)| {
-            //calc orig hash
-            let orig_file = fs::read(&original.path);
-            if let Ok(orig_file) = orig_file {
-                //let orig_hash = seahash::hash(&orig_file);
-                let orig_hash = gxhash::gxhash64(&orig_file, HASH_SEED);
-
-                //check all duplicates
-                duplicates.retain(|dup| match check_dup_hash(dup, orig_hash) {
-                    false => {
+            //stage 1: cheap prefix hash prunes any duplicate that differs early on
+            if let Some(orig_partial) = partial_hash(&original.path, algorithm) {
+                duplicates.retain(|dup| match partial_hash(&dup.path, algorithm) {
+                    Some(dup_partial) if dup_partial == orig_partial => true,
+                    _ => {
                         if print {
                             println!("\rInvalid duplicate found: {}", dup.name);
                         }
@@ -285,8 +447,27 @@ fn check_hashes(dupmap: &mut HashMap<&FileInfo, Vec<&FileInfo>>, print: bool) ->
                         *count += 1;
                         false
                     }
-                    true => true,
                 });
+
+                //stage 2: only survivors of the prefix check pay for a full read
+                if !duplicates.is_empty() {
+                    if let Some(orig_hash) = cached_full_hash(cache, original, algorithm) {
+                        duplicates.retain(|dup| {
+                            match check_dup_hash(dup, &orig_hash, cache, algorithm) {
+                                false => {
+                                    if print {
+                                        println!("\rInvalid duplicate found: {}", dup.name);
+                                    }
+                                    let _ = send.send(HashingUpdate::Refresh);
+                                    let mut count = count.lock().unwrap();
+                                    *count += 1;
+                                    false
+                                }
+                                true => true,
+                            }
+                        });
+                    }
+                }
                 //notify output thread
                 let _ = send.send(HashingUpdate::Completed);
             }
@@ -298,14 +479,79 @@ fn check_hashes(dupmap: &mut HashMap<&FileInfo, Vec<&FileInfo>>, print: bool) ->
     Arc::into_inner(count).unwrap().into_inner().unwrap()
 }
 
-fn check_dup_hash(dup: &FileInfo, orig_hash: u64) -> bool {
-    let dup_file = fs::read(&dup.path);
-    if let Ok(dup_file) = dup_file {
-        //let dup_hash = seahash::hash(&dup_file);
-        let dup_hash = gxhash::gxhash64(&dup_file, HASH_SEED);
+fn check_dup_hash(
+    dup: &FileInfo,
+    orig_hash: &Digest,
+    cache: &Mutex<HashCache>,
+    algorithm: HashAlgorithm,
+) -> bool {
+    //return false in case of error, since we cannot guarantee the duplicate
+    cached_full_hash(cache, dup, algorithm).as_ref() == Some(orig_hash)
+}
+
+//consults the on-disk cache before reading the file; a (size, mtime) match means
+//the content hasn't changed since the hash was last computed
+fn cached_full_hash(
+    cache: &Mutex<HashCache>,
+    file: &FileInfo,
+    algorithm: HashAlgorithm,
+) -> Option<Digest> {
+    if let Some(hash) = cache.lock().unwrap().get(&file.path, file.size, file.mtime) {
+        return Some(hash);
+    }
 
-        return dup_hash == orig_hash;
+    let hash = full_hash(&file.path, algorithm)?;
+    cache
+        .lock()
+        .unwrap()
+        .insert(file.path.clone(), file.size, file.mtime, hash.clone());
+    Some(hash)
+}
+
+//hashes just the first `PARTIAL_HASH_BYTES` of a file (or the whole file if smaller),
+//cheap enough to run on every candidate before committing to a full read
+fn partial_hash(path: &Path, algorithm: HashAlgorithm) -> Option<Digest> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut buf = [0u8; PARTIAL_HASH_BYTES];
+    let mut filled = 0;
+    loop {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(_) => return None,
+        }
     }
-    //return false in case of error, since we cannot guarantee the duplicate
-    false
+    let mut hasher = IncrementalHasher::new(algorithm);
+    hasher.update(&buf[..filled]);
+    Some(hasher.finalize())
+}
+
+//streams the file through the hasher in fixed-size blocks so peak memory stays
+//bounded by STREAM_BLOCK_BYTES rather than the file size. Each block is topped
+//up to a full STREAM_BLOCK_BYTES (or EOF) before being fed to the hasher, since
+//a single short `read()` would otherwise change how the bytes are chunked into
+//`update()` calls and some hashers (e.g. GxHasher) produce a different digest
+//for the same bytes depending on that chunking
+fn full_hash(path: &Path, algorithm: HashAlgorithm) -> Option<Digest> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut hasher = IncrementalHasher::new(algorithm);
+    let mut buf = [0u8; STREAM_BLOCK_BYTES];
+    loop {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(_) => return None,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        hasher.update(&buf[..filled]);
+        if filled < buf.len() {
+            break;
+        }
+    }
+    Some(hasher.finalize())
 }
@@ -0,0 +1,74 @@
+//machine-readable report of the final duplicate groups
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::FileInfo;
+
+#[derive(Serialize)]
+struct FileRecord {
+    path: PathBuf,
+    size: u64,
+}
+
+impl From<&FileInfo> for FileRecord {
+    fn from(file: &FileInfo) -> Self {
+        FileRecord {
+            path: file.path.clone(),
+            size: file.size,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DuplicateGroup {
+    original: FileRecord,
+    duplicates: Vec<FileRecord>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    duplicate_count: usize,
+    reclaimable_bytes: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    summary: Summary,
+    groups: Vec<DuplicateGroup>,
+}
+
+pub fn write_json(path: &Path, filtered: &HashMap<&FileInfo, Vec<&FileInfo>>) -> io::Result<()> {
+    let groups: Vec<DuplicateGroup> = filtered
+        .iter()
+        .map(|(original, duplicates)| DuplicateGroup {
+            original: FileRecord::from(*original),
+            duplicates: duplicates
+                .iter()
+                .map(|dup| FileRecord::from(*dup))
+                .collect(),
+        })
+        .collect();
+
+    let duplicate_count = groups.iter().map(|group| group.duplicates.len()).sum();
+    let reclaimable_bytes = groups
+        .iter()
+        .flat_map(|group| &group.duplicates)
+        .map(|dup| dup.size)
+        .sum();
+
+    let report = Report {
+        summary: Summary {
+            duplicate_count,
+            reclaimable_bytes,
+        },
+        groups,
+    };
+
+    let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+    fs::write(path, json)
+}